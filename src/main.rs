@@ -1,13 +1,149 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io::{self, BufRead};
-use std::net::{IpAddr, ToSocketAddrs};
+use std::net::{IpAddr, Ipv4Addr};
 use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use atty::Stream;
-use clap::Parser;
-use reqwest;
+use clap::{Parser, ValueEnum};
+use futures::stream::{self, StreamExt};
+use maxminddb::geoip2;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use tabled::{Table, Tabled};
+use trust_dns_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use trust_dns_resolver::TokioAsyncResolver;
+
+/// Delegated-statistics file URLs for the five regional internet registries.
+const RIR_STATS_URLS: &[&str] = &[
+    "https://ftp.apnic.net/apnic/stats/apnic/delegated-apnic-extended-latest",
+    "https://ftp.arin.net/pub/stats/arin/delegated-arin-extended-latest",
+    "https://ftp.ripe.net/pub/stats/ripencc/delegated-ripencc-extended-latest",
+    "https://ftp.afrinic.net/pub/stats/afrinic/delegated-afrinic-extended-latest",
+    "https://ftp.lacnic.net/pub/stats/lacnic/delegated-lacnic-extended-latest",
+];
+
+/// Alpha-2/alpha-3 pairs for the ISO 3166-1 codes likely to be passed to
+/// `--country`. Not exhaustive; extend as needed.
+const ISO3166: &[(&str, &str)] = &[
+    ("US", "USA"), ("GB", "GBR"), ("DE", "DEU"), ("FR", "FRA"), ("IT", "ITA"),
+    ("ES", "ESP"), ("PT", "PRT"), ("NL", "NLD"), ("BE", "BEL"), ("CH", "CHE"),
+    ("AT", "AUT"), ("SE", "SWE"), ("NO", "NOR"), ("DK", "DNK"), ("FI", "FIN"),
+    ("IE", "IRL"), ("PL", "POL"), ("CZ", "CZE"), ("GR", "GRC"), ("RU", "RUS"),
+    ("UA", "UKR"), ("TR", "TUR"), ("CN", "CHN"), ("JP", "JPN"), ("KR", "KOR"),
+    ("IN", "IND"), ("AU", "AUS"), ("NZ", "NZL"), ("CA", "CAN"), ("MX", "MEX"),
+    ("BR", "BRA"), ("AR", "ARG"), ("CL", "CHL"), ("CO", "COL"), ("PE", "PER"),
+    ("ZA", "ZAF"), ("NG", "NGA"), ("EG", "EGY"), ("KE", "KEN"), ("MA", "MAR"),
+    ("SA", "SAU"), ("AE", "ARE"), ("IL", "ISR"), ("SG", "SGP"), ("MY", "MYS"),
+    ("ID", "IDN"), ("TH", "THA"), ("VN", "VNM"), ("PH", "PHL"), ("PK", "PAK"),
+];
+
+/// Normalizes an ISO 3166-1 alpha-2 or alpha-3 country code to alpha-2,
+/// which is what RIR delegated-statistics files key records by.
+fn normalize_country_code(input: &str) -> String {
+    let upper = input.trim().to_uppercase();
+    if upper.len() == 2 {
+        return upper;
+    }
+    ISO3166
+        .iter()
+        .find(|(_, alpha3)| *alpha3 == upper)
+        .map(|(alpha2, _)| alpha2.to_string())
+        .unwrap_or(upper)
+}
+
+/// One IPv4 block delegated to `country_code`, as `start_ip` plus the number
+/// of addresses in the block.
+type Ipv4Block = (Ipv4Addr, u32);
+
+/// Downloads and parses the delegated-statistics files from all five RIRs,
+/// returning the IPv4 blocks allocated to `country_code` (an alpha-2 code).
+async fn fetch_rir_ranges(client: &reqwest::Client, country_code: &str) -> Vec<Ipv4Block> {
+    let mut blocks = Vec::new();
+
+    for url in RIR_STATS_URLS {
+        let text = match client.get(*url).timeout(Duration::from_secs(30)).send().await {
+            Ok(response) => match response.text().await {
+                Ok(text) => text,
+                Err(_) => continue,
+            },
+            Err(_) => continue,
+        };
+
+        for line in text.lines() {
+            // `registry|cc|type|start|value|date|status[|opaque-id]`
+            let fields: Vec<&str> = line.split('|').collect();
+            if fields.len() < 7 {
+                continue;
+            }
+            let (cc, kind, start, value, status) = (fields[1], fields[2], fields[3], fields[4], fields[6]);
+            if kind != "ipv4" || cc != country_code || status == "available" {
+                continue;
+            }
+            let (Ok(start_ip), Ok(count)) = (start.parse::<Ipv4Addr>(), value.parse::<u32>()) else {
+                continue;
+            };
+            blocks.push((start_ip, count));
+        }
+    }
+
+    blocks
+}
+
+/// Formats a delegated block as a CIDR (when its size is a power of two, as
+/// RIR blocks normally are) or as a `start-end` range otherwise.
+fn format_range(start: Ipv4Addr, count: u32) -> String {
+    if count > 0 && count.is_power_of_two() {
+        let prefix = 32 - count.trailing_zeros();
+        format!("{}/{}", start, prefix)
+    } else {
+        let end = u32::from(start).wrapping_add(count.saturating_sub(1));
+        format!("{}-{}", start, Ipv4Addr::from(end))
+    }
+}
+
+/// Draws `n` addresses uniformly at random from the union of `blocks`,
+/// weighting each block by its size so larger blocks are sampled more often.
+fn sample_ips(blocks: &[Ipv4Block], n: usize) -> Vec<Ipv4Addr> {
+    let total: u64 = blocks.iter().map(|(_, count)| *count as u64).sum();
+    if total == 0 {
+        return Vec::new();
+    }
+
+    let mut cumulative = Vec::with_capacity(blocks.len());
+    let mut running = 0u64;
+    for (_, count) in blocks {
+        running += *count as u64;
+        cumulative.push(running);
+    }
+
+    let mut rng = rand::thread_rng();
+    (0..n)
+        .map(|_| {
+            let pick = rng.gen_range(0..total);
+            let idx = cumulative.partition_point(|&c| c <= pick);
+            let (start, _) = blocks[idx];
+            let offset = pick - if idx == 0 { 0 } else { cumulative[idx - 1] };
+            Ipv4Addr::from(u32::from(start).wrapping_add(offset as u32))
+        })
+        .collect()
+}
+
+/// Per-request timeout for api.ipgeolocation.io lookups.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Fields pulled from an ipgeolocation.io response when no `--fields` list is given.
+const DEFAULT_FIELDS: &str = "country_name";
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    Plain,
+    Json,
+    Csv,
+    Table,
+}
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -16,15 +152,94 @@ struct Args {
     #[arg(long)]
     dig: Option<String>,
 
+    /// Path to a local MaxMind GeoLite2/GeoIP2 .mmdb database for offline lookups.
+    /// When set, api.ipgeolocation.io is never contacted and no API key is required.
+    #[arg(long)]
+    mmdb: Option<String>,
+
+    /// Directory containing ip2asn-v4.tsv and/or ip2asn-v6.tsv, used to append
+    /// ASN ownership information to each result without any API calls.
+    #[arg(long)]
+    ip2asn: Option<String>,
+
+    /// Number of api.ipgeolocation.io lookups to run concurrently.
+    #[arg(long, default_value_t = 16)]
+    concurrency: usize,
+
+    /// Comma-separated list of fields to request from the ipgeolocation.io
+    /// `fields` query parameter (e.g. "country_name,city,latitude,longitude,isp,time_zone").
+    #[arg(long)]
+    fields: Option<String>,
+
+    /// Output format for results.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Plain)]
+    output: OutputFormat,
+
+    /// Also resolve and include AAAA (IPv6) records when using --dig.
+    #[arg(long, alias = "all")]
+    ipv6: bool,
+
+    /// DNS server to query instead of the system resolver (used with --dig).
+    /// Falls back to the nameservers in /etc/resolv.conf when not given.
+    #[arg(long)]
+    resolver: Option<String>,
+
+    /// Reverse mode: instead of looking up IPs, print the IPv4 ranges
+    /// delegated to this ISO 3166-1 country code (alpha-2 or alpha-3).
+    #[arg(long)]
+    country: Option<String>,
+
+    /// With --country, print this many random addresses sampled from the
+    /// matching ranges instead of the full range list.
+    #[arg(long)]
+    sample: Option<usize>,
+
+    /// Skip the on-disk response cache entirely, always hitting the API.
+    /// Only applies to online lookups; --mmdb and --ip2asn are already
+    /// offline and never consult or populate the cache.
+    #[arg(long)]
+    no_cache: bool,
+
+    /// How long a cached lookup stays valid, in seconds. Only applies to
+    /// online lookups (see --no-cache).
+    #[arg(long, default_value_t = 3600)]
+    cache_ttl: u64,
+
     /// IP addresses to lookup (if not using --dig)
     ips: Vec<String>,
 }
 
-/// Reads the configuration file from /etc/diggeo.conf and extracts the API key.
-/// 
-/// The file should contain a line formatted like:
+/// A single lookup result, shared by the online API, offline mmdb, and
+/// ip2asn lookup paths so all output formatters have one data path.
+#[derive(Clone, Default, Serialize, Deserialize, Tabled)]
+struct GeoRecord {
+    ip: String,
+    #[tabled(display_with = "display_opt")]
+    country_name: Option<String>,
+    #[tabled(display_with = "display_opt")]
+    city: Option<String>,
+    #[tabled(display_with = "display_opt")]
+    latitude: Option<String>,
+    #[tabled(display_with = "display_opt")]
+    longitude: Option<String>,
+    #[tabled(display_with = "display_opt")]
+    isp: Option<String>,
+    #[tabled(display_with = "display_opt")]
+    time_zone: Option<String>,
+    #[tabled(display_with = "display_opt")]
+    asn: Option<String>,
+}
+
+fn display_opt(value: &Option<String>) -> String {
+    value.clone().unwrap_or_default()
+}
+
+/// Reads a single `key = value` entry from /etc/diggeo.conf.
+///
+/// The file should contain lines formatted like:
 ///   api_key = your_api_key_value
-fn read_api_key() -> Result<String, String> {
+///   mmdb_path = /path/to/GeoLite2-City.mmdb
+fn read_config_value(key: &str) -> Result<String, String> {
     let config_path = "/etc/diggeo.conf";
     let content = fs::read_to_string(config_path)
         .map_err(|err| format!("Failed to read config {}: {}", config_path, err))?;
@@ -36,69 +251,479 @@ fn read_api_key() -> Result<String, String> {
             continue;
         }
         // Look for lines containing a key and value separated by '='.
-        if let Some((key, value)) = line.split_once('=') {
-            let key = key.trim();
+        if let Some((k, value)) = line.split_once('=') {
+            let k = k.trim();
             let value = value.trim();
-            if key == "api_key" && !value.is_empty() {
+            if k == key && !value.is_empty() {
                 return Ok(value.to_string());
             }
         }
     }
 
-    Err("api_key not found in config file".to_string())
+    Err(format!("{} not found in config file", key))
+}
+
+/// Reads the API key used for api.ipgeolocation.io requests from /etc/diggeo.conf.
+fn read_api_key() -> Result<String, String> {
+    read_config_value("api_key")
+}
+
+/// Reads an mmdb database path from /etc/diggeo.conf, if one is configured.
+fn read_mmdb_path() -> Option<String> {
+    read_config_value("mmdb_path").ok()
+}
+
+/// One cached lookup result, timestamped so it can be expired by `--cache-ttl`.
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    record: GeoRecord,
+    fetched_at: u64,
+}
+
+type Cache = HashMap<String, CacheEntry>;
+
+/// Builds the cache key for an IP, folding in the requested `--fields` so a
+/// cached sparse record (e.g. from `--fields country_name`) is never handed
+/// back for a request asking for a different, richer field set.
+fn cache_key(ip: &str, fields: &str) -> String {
+    format!("{}|{}", ip, fields)
+}
+
+/// Resolves the on-disk cache file path: `cache_path` from /etc/diggeo.conf
+/// if set, otherwise `$XDG_CACHE_HOME/diggeo/cache.json`, falling back to
+/// `~/.cache/diggeo/cache.json`.
+fn cache_path() -> String {
+    if let Ok(path) = read_config_value("cache_path") {
+        return path;
+    }
+    if let Ok(xdg_cache) = std::env::var("XDG_CACHE_HOME") {
+        return format!("{}/diggeo/cache.json", xdg_cache);
+    }
+    if let Ok(home) = std::env::var("HOME") {
+        return format!("{}/.cache/diggeo/cache.json", home);
+    }
+    "/tmp/diggeo-cache.json".to_string()
+}
+
+/// Loads the cache from disk, treating a missing or unreadable file as empty.
+fn load_cache(path: &str) -> Cache {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Writes the cache back to disk, creating its parent directory if needed.
+fn save_cache(path: &str, cache: &Cache) {
+    if let Some(parent) = std::path::Path::new(path).parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    match serde_json::to_string_pretty(cache) {
+        Ok(json) => {
+            if let Err(e) = fs::write(path, json) {
+                eprintln!("Warning: failed to write cache to {}: {}", path, e);
+            }
+        }
+        Err(e) => eprintln!("Warning: failed to serialize cache: {}", e),
+    }
+}
+
+/// Seconds since the Unix epoch, used to timestamp and expire cache entries.
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Queries the ipgeolocation API for the fields requested via `--fields`
+/// (or `country_name` alone by default) and returns them as a `GeoRecord`.
+/// Only the fields `GeoRecord` holds are kept; any other requested field is
+/// dropped from the result.
+async fn get_country(
+    client: &reqwest::Client,
+    api_key: &str,
+    ip: &str,
+    fields: &str,
+) -> Result<GeoRecord, reqwest::Error> {
+    let url = format!(
+        "https://api.ipgeolocation.io/ipgeo?apiKey={}&ip={}&fields={}",
+        api_key, ip, fields
+    );
+    let response = client.get(&url).timeout(REQUEST_TIMEOUT).send().await?;
+    let json: Value = response.json().await?;
+
+    Ok(GeoRecord {
+        ip: ip.to_string(),
+        country_name: json["country_name"].as_str().map(str::to_string),
+        city: json["city"].as_str().map(str::to_string),
+        latitude: json["latitude"].as_str().map(str::to_string),
+        longitude: json["longitude"].as_str().map(str::to_string),
+        isp: json["isp"].as_str().map(str::to_string),
+        time_zone: json["time_zone"]["name"].as_str().map(str::to_string),
+        asn: None,
+    })
+}
+
+/// Looks up the country (and ASN, when available) for an IP address in a local
+/// MaxMind .mmdb database, without making any network request.
+fn get_country_offline(reader: &maxminddb::Reader<Vec<u8>>, ip: &str) -> Result<GeoRecord, String> {
+    let addr: IpAddr = ip.parse().map_err(|e| format!("invalid IP {}: {}", ip, e))?;
+
+    let city: geoip2::City = reader
+        .lookup(addr)
+        .map_err(|e| format!("mmdb lookup failed for {}: {}", ip, e))?;
+
+    let country_name = city
+        .country
+        .as_ref()
+        .and_then(|c| c.names.as_ref())
+        .and_then(|names| names.get("en"))
+        .map(|name| name.to_string());
+
+    let city_name = city
+        .city
+        .as_ref()
+        .and_then(|c| c.names.as_ref())
+        .and_then(|names| names.get("en"))
+        .map(|name| name.to_string());
+
+    let asn: Option<geoip2::Asn> = reader.lookup(addr).ok();
+    let asn_info = asn.and_then(|a| a.autonomous_system_number).map(|n| format!("AS{}", n));
+
+    Ok(GeoRecord {
+        ip: ip.to_string(),
+        country_name,
+        city: city_name,
+        asn: asn_info,
+        ..Default::default()
+    })
+}
+
+/// One non-overlapping IP range from an ip2asn-v4.tsv/ip2asn-v6.tsv dataset:
+/// `ip_start\tip_end\tasn\tcountry_code\tasn_description`.
+struct RangeRecord {
+    start: u128,
+    end: u128,
+    asn: u32,
+    country_code: String,
+    asn_description: String,
+}
+
+/// Converts an `IpAddr` into a single `u128` key so v4 and v6 ranges can be
+/// stored and binary-searched with the same comparison.
+fn ip_to_key(ip: IpAddr) -> u128 {
+    match ip {
+        IpAddr::V4(v4) => u32::from(v4) as u128,
+        IpAddr::V6(v6) => u128::from(v6),
+    }
+}
+
+/// Parses one ip2asn TSV file into records sorted by `ip_start`, ready for
+/// binary search.
+fn parse_ip2asn_file(path: &str) -> Result<Vec<RangeRecord>, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read ip2asn file {}: {}", path, err))?;
+
+    let mut records = Vec::new();
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 5 {
+            continue;
+        }
+        let (Ok(start), Ok(end)) = (fields[0].parse::<IpAddr>(), fields[1].parse::<IpAddr>()) else {
+            continue;
+        };
+        records.push(RangeRecord {
+            start: ip_to_key(start),
+            end: ip_to_key(end),
+            asn: fields[2].parse().unwrap_or(0),
+            country_code: fields[3].to_string(),
+            asn_description: fields[4].to_string(),
+        });
+    }
+    records.sort_by_key(|r| r.start);
+    Ok(records)
+}
+
+/// ip2asn ranges loaded from an `ip2asn-v4.tsv`/`ip2asn-v6.tsv` pair, kept as
+/// two separate tables so lookups can pick the one matching the queried
+/// address family.
+struct Ip2AsnTables {
+    v4: Vec<RangeRecord>,
+    v6: Vec<RangeRecord>,
+}
+
+/// Loads whichever of ip2asn-v4.tsv / ip2asn-v6.tsv exist under `dir`.
+fn load_ip2asn_tables(dir: &str) -> Result<Ip2AsnTables, String> {
+    let v4 = parse_ip2asn_file(&format!("{}/ip2asn-v4.tsv", dir)).unwrap_or_default();
+    let v6 = parse_ip2asn_file(&format!("{}/ip2asn-v6.tsv", dir)).unwrap_or_default();
+
+    if v4.is_empty() && v6.is_empty() {
+        return Err(format!("No ip2asn-v4.tsv or ip2asn-v6.tsv found under {}", dir));
+    }
+
+    Ok(Ip2AsnTables { v4, v6 })
+}
+
+/// Binary searches the table matching `ip`'s address family for the range
+/// record that contains it, i.e. the greatest `ip_start <= ip` whose
+/// `ip_end >= ip`.
+fn lookup_asn(tables: &Ip2AsnTables, ip: IpAddr) -> Option<&RangeRecord> {
+    let records = match ip {
+        IpAddr::V4(_) => &tables.v4,
+        IpAddr::V6(_) => &tables.v6,
+    };
+    let key = ip_to_key(ip);
+
+    let idx = records.partition_point(|r| r.start <= key);
+    if idx == 0 {
+        return None;
+    }
+    let candidate = &records[idx - 1];
+    (key <= candidate.end).then_some(candidate)
+}
+
+/// Fills in `record.asn` and `record.country_name` from the loaded ip2asn
+/// tables, if any, without overwriting values already set by an mmdb or
+/// online lookup.
+fn enrich_asn(record: &mut GeoRecord, tables: &Option<Ip2AsnTables>) {
+    let Some(tables) = tables.as_ref() else { return };
+    let Ok(addr) = record.ip.parse::<IpAddr>() else { return };
+    let Some(r) = lookup_asn(tables, addr) else { return };
+
+    if record.asn.is_none() {
+        record.asn = Some(format!("ASN{} ({})", r.asn, r.asn_description));
+    }
+    if record.country_name.is_none() {
+        record.country_name = Some(r.country_code.clone());
+    }
+}
+
+/// Looks up the country and ASN for an IP address purely from the loaded
+/// ip2asn tables, without any mmdb database or network request.
+fn get_country_ip2asn(tables: &Ip2AsnTables, ip: &str) -> Result<GeoRecord, String> {
+    let addr: IpAddr = ip.parse().map_err(|e| format!("invalid IP {}: {}", ip, e))?;
+    let r = lookup_asn(tables, addr).ok_or_else(|| format!("no ip2asn range covers {}", ip))?;
+
+    Ok(GeoRecord {
+        ip: ip.to_string(),
+        country_name: Some(r.country_code.clone()),
+        asn: Some(format!("ASN{} ({})", r.asn, r.asn_description)),
+        ..Default::default()
+    })
 }
 
+/// Reads `nameserver` lines from /etc/resolv.conf, in order.
+fn read_resolv_conf_nameservers() -> Vec<IpAddr> {
+    let content = match fs::read_to_string("/etc/resolv.conf") {
+        Ok(content) => content,
+        Err(_) => return Vec::new(),
+    };
 
-/// Queries the ipgeolocation API to get the country for an IP address.
-/// Returns the country name (or "Unknown" if not found).
-fn get_country(api_key: &str, ip: &str) -> Result<String, reqwest::Error> {
-    let url = format!("https://api.ipgeolocation.io/ipgeo?apiKey={}&ip={}", api_key, ip);
-    // Using reqwest's blocking API for simplicity.
-    let response = reqwest::blocking::get(&url)?;
-    let json: Value = response.json()?;
-    let country = json["country_name"].as_str().unwrap_or("Unknown");
-    Ok(country.to_string())
+    content
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .filter_map(|rest| rest.trim().parse::<IpAddr>().ok())
+        .collect()
 }
 
-/// Resolves a domain name into its (IPv4) IP addresses.
-/// This uses the standard library DNS lookup via ToSocketAddrs.
-/// (Note: unlike the bash version that uses dig + grep, here we simply collect unique IPv4 addresses.)
-fn resolve_domain(domain: &str) -> Result<Vec<IpAddr>, Box<dyn std::error::Error>> {
-    let addrs = (domain, 80).to_socket_addrs()?;
+/// Builds a resolver config pointing at `resolver_ip` when given, otherwise
+/// at the nameservers read from /etc/resolv.conf, falling back to the
+/// trust-dns default (Google public DNS) if neither yields anything.
+fn build_resolver_config(resolver_ip: Option<IpAddr>) -> ResolverConfig {
+    let nameservers = match resolver_ip {
+        Some(ip) => vec![ip],
+        None => read_resolv_conf_nameservers(),
+    };
+
+    if nameservers.is_empty() {
+        return ResolverConfig::default();
+    }
+
+    ResolverConfig::from_parts(None, vec![], NameServerConfigGroup::from_ips_clear(&nameservers, 53, true))
+}
+
+/// Resolves a domain name into its IP addresses, querying `resolver_ip` (or
+/// the nameservers in /etc/resolv.conf) instead of relying on the OS
+/// resolver. A and, when `include_ipv6` is set, AAAA records are collected
+/// and deduplicated together.
+async fn resolve_domain(
+    domain: &str,
+    resolver_ip: Option<IpAddr>,
+    include_ipv6: bool,
+) -> Result<Vec<IpAddr>, Box<dyn std::error::Error>> {
+    let resolver = TokioAsyncResolver::tokio(build_resolver_config(resolver_ip), ResolverOpts::default());
+
     let mut ips = HashSet::new();
-    for addr in addrs {
-        // Filter for IPv4 addresses only (optional).
-        if addr.ip().is_ipv4() {
-            ips.insert(addr.ip());
+    let mut last_err = None;
+
+    match resolver.ipv4_lookup(domain).await {
+        Ok(response) => ips.extend(response.iter().map(|addr| IpAddr::V4(addr.0))),
+        Err(e) => last_err = Some(e),
+    }
+
+    if include_ipv6 {
+        match resolver.ipv6_lookup(domain).await {
+            Ok(response) => ips.extend(response.iter().map(|addr| IpAddr::V6(addr.0))),
+            Err(e) => last_err = Some(e),
         }
     }
+
+    if ips.is_empty() {
+        if let Some(e) = last_err {
+            return Err(Box::new(e));
+        }
+    }
+
     Ok(ips.into_iter().collect())
 }
 
-fn main() {
+/// Quotes a CSV field when it contains a comma, quote, or newline, doubling
+/// any embedded quotes, so values like "Springfield, IL" round-trip safely.
+fn csv_field(value: &str) -> String {
+    if value.contains(',') || value.contains('"') || value.contains('\n') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Prints results in the requested format. Plain mode keeps the familiar
+/// `ip:country` line (extended with any other populated fields); the other
+/// formats share the same `GeoRecord` data.
+fn print_records(records: &[GeoRecord], format: OutputFormat) {
+    match format {
+        OutputFormat::Plain => {
+            for record in records {
+                let mut parts = Vec::new();
+                if let Some(c) = &record.country_name {
+                    parts.push(c.clone());
+                }
+                if let Some(c) = &record.city {
+                    parts.push(c.clone());
+                }
+                if let Some(lat) = &record.latitude {
+                    parts.push(lat.clone());
+                }
+                if let Some(lon) = &record.longitude {
+                    parts.push(lon.clone());
+                }
+                if let Some(isp) = &record.isp {
+                    parts.push(isp.clone());
+                }
+                if let Some(tz) = &record.time_zone {
+                    parts.push(tz.clone());
+                }
+                if let Some(asn) = &record.asn {
+                    parts.push(asn.clone());
+                }
+                if parts.is_empty() {
+                    parts.push("Unknown".to_string());
+                }
+                println!("{}:{}", record.ip, parts.join(", "));
+            }
+        }
+        OutputFormat::Json => match serde_json::to_string_pretty(records) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Error serializing results to JSON: {}", e),
+        },
+        OutputFormat::Csv => {
+            println!("ip,country_name,city,latitude,longitude,isp,time_zone,asn");
+            for record in records {
+                println!(
+                    "{},{},{},{},{},{},{},{}",
+                    csv_field(&record.ip),
+                    csv_field(&record.country_name.clone().unwrap_or_default()),
+                    csv_field(&record.city.clone().unwrap_or_default()),
+                    csv_field(&record.latitude.clone().unwrap_or_default()),
+                    csv_field(&record.longitude.clone().unwrap_or_default()),
+                    csv_field(&record.isp.clone().unwrap_or_default()),
+                    csv_field(&record.time_zone.clone().unwrap_or_default()),
+                    csv_field(&record.asn.clone().unwrap_or_default()),
+                );
+            }
+        }
+        OutputFormat::Table => {
+            println!("{}", Table::new(records));
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
     let args = Args::parse();
 
-    // Read API key from file
-    let api_key = match read_api_key() {
-        Ok(key) => key,
+    if let Some(country) = &args.country {
+        let cc = normalize_country_code(country);
+        let client = reqwest::Client::new();
+        let blocks = fetch_rir_ranges(&client, &cc).await;
+
+        if blocks.is_empty() {
+            eprintln!("No delegated ranges found for country code {}", cc);
+            process::exit(1);
+        }
+
+        match args.sample {
+            Some(n) => {
+                for ip in sample_ips(&blocks, n) {
+                    println!("{}", ip);
+                }
+            }
+            None => {
+                for (start, count) in &blocks {
+                    println!("{}", format_range(*start, *count));
+                }
+            }
+        }
+        return;
+    }
+
+    let mmdb_path = args.mmdb.clone().or_else(read_mmdb_path);
+
+    let ip2asn_tables = args.ip2asn.as_ref().map(|dir| match load_ip2asn_tables(dir) {
+        Ok(tables) => tables,
         Err(e) => {
-            eprintln!("Error reading API key: {}", e);
+            eprintln!("Error loading ip2asn tables: {}", e);
             process::exit(1);
         }
+    });
+
+    // --ip2asn alone (without --mmdb) is also a fully offline mode: country
+    // and ASN both come from the ip2asn ranges, so no API key is needed.
+    let ip2asn_only = mmdb_path.is_none() && ip2asn_tables.is_some();
+
+    // An mmdb database or an ip2asn-only run lets us skip the API key entirely.
+    let api_key = if mmdb_path.is_some() || ip2asn_only {
+        None
+    } else {
+        match read_api_key() {
+            Ok(key) => Some(key),
+            Err(e) => {
+                eprintln!("Error reading API key: {}", e);
+                process::exit(1);
+            }
+        }
     };
 
     let mut ips: Vec<String> = Vec::new();
 
     if let Some(domain) = args.dig {
+        let resolver_ip = match args.resolver.as_deref().map(|s| s.parse::<IpAddr>()) {
+            Some(Ok(ip)) => Some(ip),
+            Some(Err(e)) => {
+                eprintln!("Invalid --resolver address: {}", e);
+                process::exit(1);
+            }
+            None => None,
+        };
+
         // In domain resolution mode â€“ resolve the domain to IP addresses.
-        match resolve_domain(&domain) {
+        match resolve_domain(&domain, resolver_ip, args.ipv6).await {
             Ok(resolved) if !resolved.is_empty() => {
                 for ip in resolved {
                     ips.push(ip.to_string());
                 }
             }
             Ok(_) => {
-                eprintln!("No IPv4 addresses found for domain: {}", domain);
+                eprintln!("No addresses found for domain: {}", domain);
                 process::exit(1);
             }
             Err(e) => {
@@ -131,14 +756,111 @@ fn main() {
         eprintln!("  diggeo 8.8.8.8 1.1.1.1");
         eprintln!("  cat ips.txt | diggeo");
         eprintln!("  diggeo --dig example.com");
+        eprintln!("  diggeo --dig example.com --ipv6 --resolver 1.1.1.1");
+        eprintln!("  diggeo --country DE --sample 5");
+        eprintln!("  diggeo --cache-ttl 86400 8.8.8.8   (add --no-cache to bypass)");
+        eprintln!("  diggeo --mmdb /path/to/GeoLite2-City.mmdb 8.8.8.8");
+        eprintln!("  diggeo --ip2asn /path/to/ip2asn-data 8.8.8.8");
+        eprintln!("  diggeo --fields country_name,city,isp --output table 8.8.8.8");
         process::exit(1);
     }
 
-    // Process each IP address and display the result.
-    for ip in ips {
-        match get_country(&api_key, &ip) {
-            Ok(country) => println!("{}:{}", ip, country),
+    if let Some(path) = mmdb_path {
+        let reader = match maxminddb::Reader::open_readfile(&path) {
+            Ok(reader) => reader,
+            Err(e) => {
+                eprintln!("Failed to open mmdb database {}: {}", path, e);
+                process::exit(1);
+            }
+        };
+
+        let mut records = Vec::new();
+        for ip in ips {
+            match get_country_offline(&reader, &ip) {
+                Ok(mut record) => {
+                    enrich_asn(&mut record, &ip2asn_tables);
+                    records.push(record);
+                }
+                Err(e) => eprintln!("Error looking up {}: {}", ip, e),
+            }
+        }
+        print_records(&records, args.output);
+        return;
+    }
+
+    if ip2asn_only {
+        let tables = ip2asn_tables.as_ref().expect("ip2asn tables must be loaded for ip2asn-only mode");
+
+        let mut records = Vec::new();
+        for ip in ips {
+            match get_country_ip2asn(tables, &ip) {
+                Ok(record) => records.push(record),
+                Err(e) => eprintln!("Error looking up {}: {}", ip, e),
+            }
+        }
+        print_records(&records, args.output);
+        return;
+    }
+
+    // Process every IP concurrently, bounded by --concurrency, while keeping
+    // output in the original order by indexing into `records_by_index`.
+    let api_key = api_key.expect("api key must be set when not using --mmdb or --ip2asn");
+    let client = reqwest::Client::new();
+    let fields = args.fields.unwrap_or_else(|| DEFAULT_FIELDS.to_string());
+
+    let cache_enabled = !args.no_cache;
+    let path = cache_path();
+    let mut cache = if cache_enabled { load_cache(&path) } else { Cache::new() };
+    let now = unix_now();
+
+    let mut records_by_index: Vec<Option<GeoRecord>> = vec![None; ips.len()];
+    let mut to_fetch = Vec::new();
+    for (index, ip) in ips.into_iter().enumerate() {
+        let cached = cache_enabled
+            .then(|| cache.get(&cache_key(&ip, &fields)))
+            .flatten()
+            .filter(|entry| now.saturating_sub(entry.fetched_at) < args.cache_ttl);
+        match cached {
+            Some(entry) => records_by_index[index] = Some(entry.record.clone()),
+            None => to_fetch.push((index, ip)),
+        }
+    }
+
+    let fetched: Vec<(usize, String, Result<GeoRecord, reqwest::Error>)> = stream::iter(to_fetch)
+        .map(|(index, ip)| {
+            let client = client.clone();
+            let api_key = api_key.clone();
+            let fields = fields.clone();
+            async move {
+                let result = get_country(&client, &api_key, &ip, &fields).await;
+                (index, ip, result)
+            }
+        })
+        .buffer_unordered(args.concurrency)
+        .collect()
+        .await;
+
+    for (index, ip, result) in fetched {
+        match result {
+            // A failed lookup is isolated to this one IP and never aborts the batch.
+            Ok(record) => {
+                if cache_enabled {
+                    cache.insert(cache_key(&ip, &fields), CacheEntry { record: record.clone(), fetched_at: now });
+                }
+                records_by_index[index] = Some(record);
+            }
             Err(e) => eprintln!("Error fetching geolocation for {}: {}", ip, e),
         }
     }
+
+    if cache_enabled {
+        save_cache(&path, &cache);
+    }
+
+    let mut records = Vec::new();
+    for mut record in records_by_index.into_iter().flatten() {
+        enrich_asn(&mut record, &ip2asn_tables);
+        records.push(record);
+    }
+    print_records(&records, args.output);
 }